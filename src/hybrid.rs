@@ -0,0 +1,285 @@
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::lmsr::{LmsrError, LmsrMarket};
+use crate::market::Market;
+use crate::order_book::{LimitOrder, OrderBook};
+
+/// Breakdown of where a trade's shares were filled from: resting limit
+/// orders in the book (peer-to-peer, no AMM liquidity cost) and/or the
+/// underlying LMSR market maker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillBreakdown {
+    pub shares_from_book: u64,
+    pub amount_from_book: f64,
+    pub shares_from_amm: u64,
+    pub amount_from_amm: f64,
+}
+
+/// Routes trades between a resting limit order book and an `LmsrMarket`
+/// automated market maker, filling against whichever source gives the
+/// trader the better price: resting orders that beat the AMM's current
+/// marginal price are filled first, with only the remainder routed through
+/// the AMM.
+pub struct HybridMarket<T: EnumCount + IntoEnumIterator + Copy + Eq> {
+    amm: LmsrMarket<T>,
+    book: OrderBook<T>,
+}
+
+impl<T> HybridMarket<T>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    pub fn new(liquidity: f64) -> Self {
+        Self {
+            amm: LmsrMarket::new(liquidity),
+            book: OrderBook::new(),
+        }
+    }
+
+    /// Adds a resting limit order to the book.
+    pub fn place_limit_order(&mut self, order: LimitOrder<T>) {
+        self.book.place(order);
+    }
+
+    pub fn buy(&mut self, outcome: T, amount: u64) -> Result<FillBreakdown, LmsrError> {
+        if self.amm.resolved().is_some() {
+            return Err(LmsrError::Resolved);
+        }
+
+        let mut remaining = amount;
+        let mut shares_from_book = 0;
+        let mut amount_from_book = 0.0;
+        let mut shares_from_amm = 0;
+        let mut amount_from_amm = 0.0;
+
+        while remaining > 0 {
+            let marginal_price = self.amm.price(outcome)?;
+            if let Some((filled, cost)) = self.book.fill_best_ask(outcome, marginal_price, remaining)
+            {
+                shares_from_book += filled;
+                amount_from_book += cost;
+                remaining -= filled;
+                continue;
+            }
+
+            // No resting ask beats the current AMM price. Buy a single share
+            // from the AMM and re-check the book afterwards, since the AMM
+            // price moves with every share bought and may cross a resting
+            // ask that was previously out of reach.
+            amount_from_amm += self.amm.buy(outcome, 1)?;
+            shares_from_amm += 1;
+            remaining -= 1;
+        }
+
+        Ok(FillBreakdown {
+            shares_from_book,
+            amount_from_book,
+            shares_from_amm,
+            amount_from_amm,
+        })
+    }
+
+    pub fn sell(&mut self, outcome: T, amount: u64) -> Result<FillBreakdown, LmsrError> {
+        if self.amm.resolved().is_some() {
+            return Err(LmsrError::Resolved);
+        }
+
+        let mut remaining = amount;
+        let mut shares_from_book = 0;
+        let mut amount_from_book = 0.0;
+        let mut shares_from_amm = 0;
+        let mut amount_from_amm = 0.0;
+
+        while remaining > 0 {
+            let marginal_price = self.amm.price(outcome)?;
+            if let Some((filled, revenue)) =
+                self.book.fill_best_bid(outcome, marginal_price, remaining)
+            {
+                shares_from_book += filled;
+                amount_from_book += revenue;
+                remaining -= filled;
+                continue;
+            }
+
+            // No resting bid beats the current AMM price. Sell a single
+            // share to the AMM and re-check the book afterwards, since the
+            // AMM price moves with every share sold and may cross a resting
+            // bid that was previously out of reach.
+            amount_from_amm += self.amm.sell(outcome, 1)?;
+            shares_from_amm += 1;
+            remaining -= 1;
+        }
+
+        Ok(FillBreakdown {
+            shares_from_book,
+            amount_from_book,
+            shares_from_amm,
+            amount_from_amm,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::{EnumCount, EnumIter};
+
+    use crate::order_book::Side;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumCount, EnumIter)]
+    enum ThreeOutcome {
+        A,
+        B,
+        C,
+    }
+
+    #[test]
+    fn test_buy_fills_cheap_ask_before_amm() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(10.0);
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Sell,
+            limit_price: marginal_price / 2.0,
+            shares: 3,
+        });
+
+        let fill = market.buy(ThreeOutcome::A, 3).expect("could not buy");
+
+        assert_eq!(fill.shares_from_book, 3);
+        assert_eq!(fill.shares_from_amm, 0);
+        assert_eq!(fill.amount_from_book, 3.0 * marginal_price / 2.0);
+    }
+
+    #[test]
+    fn test_buy_routes_remainder_through_amm() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(10.0);
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Sell,
+            limit_price: marginal_price / 2.0,
+            shares: 2,
+        });
+
+        let fill = market.buy(ThreeOutcome::A, 5).expect("could not buy");
+
+        assert_eq!(fill.shares_from_book, 2);
+        assert_eq!(fill.shares_from_amm, 3);
+        assert!(fill.amount_from_amm > 0.0);
+    }
+
+    #[test]
+    fn test_buy_ignores_asks_priced_above_amm() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(10.0);
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Sell,
+            limit_price: marginal_price * 2.0,
+            shares: 3,
+        });
+
+        let fill = market.buy(ThreeOutcome::A, 3).expect("could not buy");
+
+        assert_eq!(fill.shares_from_book, 0);
+        assert_eq!(fill.shares_from_amm, 3);
+    }
+
+    #[test]
+    fn test_sell_fills_rich_bid_before_amm() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(10.0);
+        market
+            .amm
+            .buy(ThreeOutcome::A, 5)
+            .expect("could not seed shares");
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Buy,
+            limit_price: marginal_price * 2.0,
+            shares: 2,
+        });
+
+        let fill = market.sell(ThreeOutcome::A, 2).expect("could not sell");
+
+        assert_eq!(fill.shares_from_book, 2);
+        assert_eq!(fill.shares_from_amm, 0);
+        assert_eq!(fill.amount_from_book, 2.0 * marginal_price * 2.0);
+    }
+
+    #[test]
+    fn test_buy_crosses_ask_once_amm_price_moves_past_it() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(1.0);
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        // This ask is priced above the initial AMM price, so it starts out
+        // unreachable, but buying from the AMM should push the price past
+        // it well before the resting 100 shares could ever be exhausted.
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Sell,
+            limit_price: marginal_price + 0.01,
+            shares: 100,
+        });
+
+        let fill = market.buy(ThreeOutcome::A, 5).expect("could not buy");
+
+        assert!(fill.shares_from_book > 0);
+    }
+
+    #[test]
+    fn test_buy_on_resolved_market_fails() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(10.0);
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Sell,
+            limit_price: marginal_price / 2.0,
+            shares: 3,
+        });
+        market
+            .amm
+            .resolve(ThreeOutcome::A)
+            .expect("could not resolve market");
+
+        let err = market
+            .buy(ThreeOutcome::A, 3)
+            .expect_err("buying on a resolved market should fail");
+
+        assert_eq!(err, LmsrError::Resolved);
+    }
+
+    #[test]
+    fn test_sell_on_resolved_market_fails() {
+        let mut market = HybridMarket::<ThreeOutcome>::new(10.0);
+        market
+            .amm
+            .buy(ThreeOutcome::A, 5)
+            .expect("could not seed shares");
+        let marginal_price = market.amm.price(ThreeOutcome::A).expect("no price");
+
+        market.place_limit_order(LimitOrder {
+            outcome: ThreeOutcome::A,
+            side: Side::Buy,
+            limit_price: marginal_price * 2.0,
+            shares: 2,
+        });
+        market
+            .amm
+            .resolve(ThreeOutcome::A)
+            .expect("could not resolve market");
+
+        let err = market
+            .sell(ThreeOutcome::A, 2)
+            .expect_err("selling on a resolved market should fail");
+
+        assert_eq!(err, LmsrError::Resolved);
+    }
+}
@@ -1,12 +1,16 @@
 use strum::{EnumCount, IntoEnumIterator};
 
-use crate::market::Market;
+use crate::market::{ComboTrade, CombinatorialMarket, Market};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum LmsrError {
     InsufficientShares,
     Resolved,
     NegativeMarketCapitalization,
+    InvalidPartition,
+    InvalidBudget,
+    Unresolved,
+    AlreadyClaimed,
 }
 
 /// Used for serialization
@@ -15,6 +19,8 @@ pub struct LmsrMarketDTO<T: EnumCount + IntoEnumIterator + Copy + Eq> {
     pub liquidity: f64,
     pub resolved: Option<T>,
     pub market_volume: f64,
+    pub fee: f64,
+    pub fees_collected: f64,
 }
 
 impl<T> From<LmsrMarket<T>> for LmsrMarketDTO<T>
@@ -27,6 +33,8 @@ where
             liquidity: value.liquidity,
             resolved: value.resolved,
             market_volume: value.market_volume,
+            fee: value.fee,
+            fees_collected: value.fees_collected,
         }
     }
 }
@@ -41,6 +49,8 @@ where
             liquidity: value.liquidity,
             resolved: value.resolved,
             market_volume: value.market_volume,
+            fee: value.fee,
+            fees_collected: value.fees_collected,
         }
     }
 }
@@ -49,6 +59,8 @@ pub struct LmsrMarket<T: EnumCount + IntoEnumIterator + Copy + Eq> {
     liquidity: f64,
     resolved: Option<T>,
     market_volume: f64,
+    fee: f64,
+    fees_collected: f64,
 }
 
 impl<T> LmsrMarket<T>
@@ -56,27 +68,157 @@ where
     T: EnumCount + IntoEnumIterator + Copy + Eq,
 {
     pub fn new(liquidity: f64) -> Self {
+        Self::new_with_fee(liquidity, 0)
+    }
+
+    /// Creates a market that charges `fee_bps` basis points (1 bps = 0.01%)
+    /// on top of every buy, and withholds the same fraction from every sell,
+    /// so the market maker is self-sustaining instead of giving liquidity
+    /// away for free. The collected amount is tracked separately via
+    /// `fees_collected` and never affects `market_volume` or payouts.
+    pub fn new_with_fee(liquidity: f64, fee_bps: u32) -> Self {
         Self {
             shares: vec![0; T::COUNT],
             liquidity,
             resolved: None,
             market_volume: 0.0,
+            fee: fee_bps as f64 / 10_000.0,
+            fees_collected: 0.0,
         }
     }
 
+    /// Total fees withheld from trades so far.
+    pub fn fees_collected(&self) -> f64 {
+        self.fees_collected
+    }
+
+    /// The winning outcome, if the market has been resolved.
+    pub fn resolved(&self) -> Option<T> {
+        self.resolved
+    }
+
     pub fn outcome_index(outcome: T) -> usize {
         T::iter()
             .position(|o| o == outcome)
             .expect("Invalid outcome")
     }
 
+    /// Computes the exponents `exp(q_i / liquidity)` for every outcome in a
+    /// numerically stable way (the log-sum-exp trick): returns the maximum
+    /// exponent `m = max_i(q_i / liquidity)` together with the shifted values
+    /// `exp(q_i / liquidity - m)`, each at most 1, so nothing overflows
+    /// even when some outcome's shares vastly exceed `liquidity`. The true
+    /// exponents are the returned values scaled by `exp(m)`.
+    fn stabilized_exponents(shares: &[u64], liquidity: f64) -> (f64, Vec<f64>) {
+        let m = shares
+            .iter()
+            .map(|&q| q as f64 / liquidity)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let exponents = shares
+            .iter()
+            .map(|&q| (q as f64 / liquidity - m).exp())
+            .collect();
+
+        (m, exponents)
+    }
+
     fn cost(&self, shares: &[u64]) -> f64 {
-        let sum: f64 = shares
+        let (m, exponents) = Self::stabilized_exponents(shares, self.liquidity);
+        let sum: f64 = exponents.iter().sum();
+
+        self.liquidity * (m + sum.ln())
+    }
+
+    /// Checks that `buy_set`, `sell_set` and `keep_set` are pairwise disjoint
+    /// and together cover every outcome exactly once, and that `buy_set` and
+    /// `sell_set` are both non-empty.
+    fn validate_partition(
+        buy_set: &[T],
+        sell_set: &[T],
+        keep_set: &[T],
+    ) -> Result<(), LmsrError> {
+        if buy_set.is_empty() || sell_set.is_empty() {
+            return Err(LmsrError::InvalidPartition);
+        }
+
+        let mut covered = vec![false; T::COUNT];
+        for &outcome in buy_set.iter().chain(sell_set).chain(keep_set) {
+            let i = Self::outcome_index(outcome);
+            if covered[i] {
+                return Err(LmsrError::InvalidPartition);
+            }
+            covered[i] = true;
+        }
+
+        if covered.iter().any(|&c| !c) {
+            return Err(LmsrError::InvalidPartition);
+        }
+
+        Ok(())
+    }
+
+    /// The combined price of a set of outcomes, i.e. the sum of their
+    /// individual prices.
+    fn combined_price(&self, outcomes: &[T]) -> Result<f64, LmsrError> {
+        outcomes
             .iter()
-            .map(|&q| (q as f64 / self.liquidity).exp())
-            .sum();
+            .try_fold(0.0, |acc, &outcome| Ok(acc + self.price(outcome)?))
+    }
 
-        self.liquidity * sum.ln()
+    /// Solves the LMSR cost function for the number of shares of `outcome`
+    /// that `budget` currency buys, i.e. the largest `delta` such that
+    /// `cost(q + delta * e_outcome) - cost(q) <= budget`.
+    ///
+    /// Derived from the closed form for a single outcome,
+    /// `exp(delta/liquidity) = (S * (exp(budget/liquidity) - 1) + exp(q_i/liquidity)) / exp(q_i/liquidity)`,
+    /// rewritten in the stabilized basis (dividing through by the shared
+    /// `exp(m)` factor) as
+    /// `delta = liquidity * ln((e_sum * expm1(budget/liquidity) + e_i) / e_i)`,
+    /// using `expm1` to keep the `exp(budget/liquidity) - 1` term accurate
+    /// for small budgets.
+    pub fn shares_for_budget(&self, outcome: T, budget: f64) -> Result<u64, LmsrError> {
+        if self.resolved.is_some() {
+            return Err(LmsrError::Resolved);
+        }
+
+        let i = Self::outcome_index(outcome);
+        let (_, exponents) = Self::stabilized_exponents(&self.shares, self.liquidity);
+        let e_i = exponents[i];
+        let e_sum: f64 = exponents.iter().sum();
+
+        let a = budget / self.liquidity;
+        let numerator = e_sum * a.exp_m1() + e_i;
+        if numerator <= 0.0 {
+            return Err(LmsrError::InvalidBudget);
+        }
+
+        let delta = self.liquidity * (numerator / e_i).ln();
+        if !delta.is_finite() || delta < 0.0 {
+            return Err(LmsrError::InvalidBudget);
+        }
+
+        Ok(delta.floor() as u64)
+    }
+
+    /// Buys as many shares of `outcome` as `budget` affords, returning the
+    /// number of shares bought and their actual cost (which is at most
+    /// `budget`, since `shares_for_budget` floors to an integer number of
+    /// shares). Accounts for the market's fee by solving for the pre-fee
+    /// budget that leaves room for `buy`'s `(1 + fee)` surcharge.
+    pub fn buy_with_budget(
+        &mut self,
+        outcome: T,
+        budget: f64,
+    ) -> Result<(u64, f64), LmsrError> {
+        let pre_fee_budget = budget / (1.0 + self.fee);
+        let shares = self.shares_for_budget(outcome, pre_fee_budget)?;
+        if shares == 0 {
+            return Ok((0, 0.0));
+        }
+
+        let cost = self.buy(outcome, shares)?;
+        Ok((shares, cost))
     }
 
     pub fn serialize(self) -> LmsrMarketDTO<T> {
@@ -93,16 +235,10 @@ where
 
     fn price(&self, outcome: Self::Outcome) -> Result<f64, Self::Error> {
         let i = Self::outcome_index(outcome);
-        let q_i = self.shares[i] as f64;
+        let (_, exponents) = Self::stabilized_exponents(&self.shares, self.liquidity);
+        let denom: f64 = exponents.iter().sum();
 
-        let exp_qi = (q_i / self.liquidity).exp();
-        let denom: f64 = self
-            .shares
-            .iter()
-            .map(|&q| (q as f64 / self.liquidity).exp())
-            .sum();
-
-        Ok(exp_qi / denom)
+        Ok(exponents[i] / denom)
     }
 
     fn buy(&mut self, outcome: Self::Outcome, amount: u64) -> Result<f64, Self::Error> {
@@ -116,9 +252,13 @@ where
         new_shares[i] += amount;
 
         let new_cost = self.cost(&new_shares);
+        let cost_delta = new_cost - current_cost;
+        let fee = cost_delta * self.fee;
+
         self.shares = new_shares;
-        self.market_volume += new_cost - current_cost;
-        Ok(new_cost - current_cost)
+        self.market_volume += cost_delta;
+        self.fees_collected += fee;
+        Ok(cost_delta + fee)
     }
 
     fn sell(&mut self, outcome: Self::Outcome, amount: u64) -> Result<f64, Self::Error> {
@@ -137,12 +277,16 @@ where
         new_shares[i] -= amount;
 
         let new_cost = self.cost(&new_shares);
-        if self.market_volume - (current_cost - new_cost) < 0.0 {
+        let revenue_delta = current_cost - new_cost;
+        if self.market_volume - revenue_delta < 0.0 {
             return Err(LmsrError::NegativeMarketCapitalization);
         }
-        self.market_volume -= current_cost - new_cost;
+        let fee = revenue_delta * self.fee;
+
+        self.market_volume -= revenue_delta;
+        self.fees_collected += fee;
         self.shares = new_shares;
-        Ok(current_cost - new_cost)
+        Ok(revenue_delta - fee)
     }
 
     fn resolve(&mut self, winning_outcome: Self::Outcome) -> Result<(), Self::Error> {
@@ -161,12 +305,86 @@ where
     }
 }
 
+impl<T> CombinatorialMarket for LmsrMarket<T>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    fn buy_combo(
+        &mut self,
+        buy_set: &[Self::Outcome],
+        sell_set: &[Self::Outcome],
+        keep_set: &[Self::Outcome],
+        amount: u64,
+    ) -> Result<ComboTrade, Self::Error> {
+        if self.resolved.is_some() {
+            return Err(LmsrError::Resolved);
+        }
+        Self::validate_partition(buy_set, sell_set, keep_set)?;
+
+        for &outcome in sell_set {
+            let i = Self::outcome_index(outcome);
+            if amount > self.shares[i] {
+                return Err(LmsrError::InsufficientShares);
+            }
+        }
+
+        let combined_price = self.combined_price(buy_set)?;
+        let current_cost = self.cost(&self.shares);
+
+        let mut new_shares = self.shares.clone();
+        for &outcome in buy_set {
+            new_shares[Self::outcome_index(outcome)] += amount;
+        }
+        for &outcome in sell_set {
+            new_shares[Self::outcome_index(outcome)] -= amount;
+        }
+
+        let new_cost = self.cost(&new_shares);
+        let net_cost = new_cost - current_cost;
+        if self.market_volume + net_cost < 0.0 {
+            return Err(LmsrError::NegativeMarketCapitalization);
+        }
+        // Mirrors `buy`/`sell`'s fee treatment in a single signed formula: a
+        // net cost (net buy) is surcharged by `fee`, a net proceeds (net
+        // sell) is withheld by `fee`.
+        let fee = net_cost.abs() * self.fee;
+
+        self.shares = new_shares;
+        self.market_volume += net_cost;
+        self.fees_collected += fee;
+
+        Ok(ComboTrade {
+            net_cost: net_cost + fee,
+            combined_price,
+        })
+    }
+
+    fn sell_combo(
+        &mut self,
+        buy_set: &[Self::Outcome],
+        sell_set: &[Self::Outcome],
+        keep_set: &[Self::Outcome],
+        amount: u64,
+    ) -> Result<ComboTrade, Self::Error> {
+        self.buy_combo(sell_set, buy_set, keep_set, amount)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use strum::{EnumCount, EnumIter};
+
     use crate::market::BinaryOutcome;
 
     use super::*;
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumCount, EnumIter)]
+    enum ThreeOutcome {
+        A,
+        B,
+        C,
+    }
+
     #[test]
     fn test_price_equal() {
         let market = LmsrMarket::<BinaryOutcome>::new(10.0);
@@ -307,4 +525,285 @@ mod tests {
                 * (shares / 2) as f64
         );
     }
+
+    #[test]
+    fn test_price_finite_with_large_shares() {
+        let mut market = LmsrMarket::<BinaryOutcome>::new(10.0);
+
+        market
+            .buy(BinaryOutcome::Yes, 10_000)
+            .expect("could not buy");
+
+        let price_yes = market
+            .price(BinaryOutcome::Yes)
+            .expect("could not determine yes price");
+        let price_no = market
+            .price(BinaryOutcome::No)
+            .expect("could not determine no price");
+
+        assert!(price_yes.is_finite());
+        assert!(price_no.is_finite());
+        assert!((price_yes + price_no - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_buy_finite_with_large_shares() {
+        let mut market = LmsrMarket::<BinaryOutcome>::new(10.0);
+
+        let cost = market
+            .buy(BinaryOutcome::Yes, 10_000)
+            .expect("could not buy");
+
+        assert!(cost.is_finite());
+    }
+
+    #[test]
+    fn test_combo_rejects_overlapping_sets() {
+        let mut market = LmsrMarket::<ThreeOutcome>::new(10.0);
+
+        let err = market
+            .buy_combo(
+                &[ThreeOutcome::A, ThreeOutcome::B],
+                &[ThreeOutcome::B, ThreeOutcome::C],
+                &[],
+                1,
+            )
+            .expect_err("overlapping sets should be rejected");
+
+        assert_eq!(err, LmsrError::InvalidPartition);
+    }
+
+    #[test]
+    fn test_combo_rejects_incomplete_partition() {
+        let mut market = LmsrMarket::<ThreeOutcome>::new(10.0);
+
+        let err = market
+            .buy_combo(&[ThreeOutcome::A], &[ThreeOutcome::B], &[], 1)
+            .expect_err("partition missing an outcome should be rejected");
+
+        assert_eq!(err, LmsrError::InvalidPartition);
+    }
+
+    #[test]
+    fn test_combo_rejects_empty_buy_or_sell_set() {
+        let mut market = LmsrMarket::<ThreeOutcome>::new(10.0);
+
+        let err = market
+            .buy_combo(
+                &[],
+                &[ThreeOutcome::A, ThreeOutcome::B, ThreeOutcome::C],
+                &[],
+                1,
+            )
+            .expect_err("empty buy set should be rejected");
+
+        assert_eq!(err, LmsrError::InvalidPartition);
+    }
+
+    #[test]
+    fn test_buy_combo_raises_combined_price() {
+        let mut market = LmsrMarket::<ThreeOutcome>::new(10.0);
+        market.buy(ThreeOutcome::C, 5).expect("could not buy");
+
+        let before = market
+            .combined_price(&[ThreeOutcome::A, ThreeOutcome::B])
+            .expect("could not determine combined price");
+
+        market
+            .buy_combo(
+                &[ThreeOutcome::A, ThreeOutcome::B],
+                &[ThreeOutcome::C],
+                &[],
+                5,
+            )
+            .expect("could not buy combo");
+
+        let after = market
+            .combined_price(&[ThreeOutcome::A, ThreeOutcome::B])
+            .expect("could not determine combined price");
+
+        assert!(after > before);
+    }
+
+    #[test]
+    fn test_sell_combo_inverts_buy_combo() {
+        let mut market = LmsrMarket::<ThreeOutcome>::new(10.0);
+
+        // Seed outstanding shares of C so the combo trade below has
+        // something to sell against.
+        market.buy(ThreeOutcome::C, 5).expect("could not buy");
+
+        let bought = market
+            .buy_combo(
+                &[ThreeOutcome::A, ThreeOutcome::B],
+                &[ThreeOutcome::C],
+                &[],
+                5,
+            )
+            .expect("could not buy combo");
+
+        let sold = market
+            .sell_combo(
+                &[ThreeOutcome::A, ThreeOutcome::B],
+                &[ThreeOutcome::C],
+                &[],
+                5,
+            )
+            .expect("could not sell combo");
+
+        assert_eq!(bought.net_cost, -sold.net_cost);
+        assert_eq!(market.shares, vec![0, 0, 5]);
+    }
+
+    #[test]
+    fn test_fee_round_trip_costs_double_fee() {
+        let mut reference = LmsrMarket::<BinaryOutcome>::new(10.0);
+        let cost_delta = reference
+            .buy(BinaryOutcome::Yes, 1)
+            .expect("could not buy");
+
+        let mut market = LmsrMarket::<BinaryOutcome>::new_with_fee(10.0, 100);
+        let cost = market.buy(BinaryOutcome::Yes, 1).expect("could not buy");
+        let revenue = market.sell(BinaryOutcome::Yes, 1).expect("could not sell");
+
+        assert!((cost - revenue - 2.0 * 0.01 * cost_delta).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fees_excluded_from_payout() {
+        let mut with_fee = LmsrMarket::<BinaryOutcome>::new_with_fee(10.0, 500);
+        let mut without_fee = LmsrMarket::<BinaryOutcome>::new(10.0);
+
+        with_fee.buy(BinaryOutcome::Yes, 4).expect("could not buy");
+        without_fee.buy(BinaryOutcome::Yes, 4).expect("could not buy");
+
+        assert!(with_fee.fees_collected() > 0.0);
+
+        with_fee
+            .resolve(BinaryOutcome::Yes)
+            .expect("could not resolve market");
+        without_fee
+            .resolve(BinaryOutcome::Yes)
+            .expect("could not resolve market");
+
+        assert_eq!(
+            with_fee.payout_per_share(BinaryOutcome::Yes),
+            without_fee.payout_per_share(BinaryOutcome::Yes)
+        );
+    }
+
+    #[test]
+    fn test_shares_for_budget_matches_actual_cost() {
+        let market = LmsrMarket::<BinaryOutcome>::new(10.0);
+
+        let shares = market
+            .shares_for_budget(BinaryOutcome::Yes, 5.0)
+            .expect("could not solve for shares");
+
+        let mut reference = LmsrMarket::<BinaryOutcome>::new(10.0);
+        let cost = reference
+            .buy(BinaryOutcome::Yes, shares)
+            .expect("could not buy");
+        let cost_one_more = reference
+            .buy(BinaryOutcome::Yes, 1)
+            .expect("could not buy");
+
+        assert!(cost <= 5.0);
+        assert!(cost + cost_one_more > 5.0);
+    }
+
+    #[test]
+    fn test_shares_for_budget_zero_below_marginal_price() {
+        let market = LmsrMarket::<BinaryOutcome>::new(10.0);
+
+        let shares = market
+            .shares_for_budget(BinaryOutcome::Yes, 1e-9)
+            .expect("could not solve for shares");
+
+        assert_eq!(shares, 0);
+    }
+
+    #[test]
+    fn test_shares_for_budget_matches_actual_cost_on_traded_market() {
+        let mut market = LmsrMarket::<BinaryOutcome>::new(10.0);
+        // Trade on the market first so `shares` is non-zero, exercising the
+        // general closed form rather than the degenerate fresh-market case.
+        market.buy(BinaryOutcome::Yes, 6).expect("could not buy");
+
+        let shares = market
+            .shares_for_budget(BinaryOutcome::Yes, 5.0)
+            .expect("could not solve for shares");
+
+        let mut reference = LmsrMarket::<BinaryOutcome>::new(10.0);
+        reference
+            .buy(BinaryOutcome::Yes, 6)
+            .expect("could not buy");
+        let cost = reference
+            .buy(BinaryOutcome::Yes, shares)
+            .expect("could not buy");
+        let cost_one_more = reference
+            .buy(BinaryOutcome::Yes, 1)
+            .expect("could not buy");
+
+        assert!(cost <= 5.0);
+        assert!(cost + cost_one_more > 5.0);
+    }
+
+    #[test]
+    fn test_buy_with_budget_spends_at_most_budget() {
+        let mut market = LmsrMarket::<BinaryOutcome>::new(10.0);
+
+        let (shares, cost) = market
+            .buy_with_budget(BinaryOutcome::Yes, 5.0)
+            .expect("could not buy with budget");
+
+        assert!(shares > 0);
+        assert!(cost <= 5.0);
+        let i = LmsrMarket::<BinaryOutcome>::outcome_index(BinaryOutcome::Yes);
+        assert_eq!(market.shares[i], shares);
+    }
+
+    #[test]
+    fn test_buy_with_budget_spends_at_most_budget_with_fee() {
+        let mut market = LmsrMarket::<BinaryOutcome>::new_with_fee(10.0, 500);
+
+        let (shares, cost) = market
+            .buy_with_budget(BinaryOutcome::Yes, 5.0)
+            .expect("could not buy with budget");
+
+        assert!(shares > 0);
+        assert!(cost <= 5.0);
+    }
+
+    #[test]
+    fn test_combo_trade_charges_fee() {
+        let mut with_fee = LmsrMarket::<ThreeOutcome>::new_with_fee(10.0, 500);
+        let mut without_fee = LmsrMarket::<ThreeOutcome>::new(10.0);
+
+        with_fee.buy(ThreeOutcome::C, 5).expect("could not buy");
+        without_fee.buy(ThreeOutcome::C, 5).expect("could not buy");
+        let fees_before_combo = with_fee.fees_collected();
+
+        let with_fee_trade = with_fee
+            .buy_combo(
+                &[ThreeOutcome::A, ThreeOutcome::B],
+                &[ThreeOutcome::C],
+                &[],
+                5,
+            )
+            .expect("could not buy combo");
+        let without_fee_trade = without_fee
+            .buy_combo(
+                &[ThreeOutcome::A, ThreeOutcome::B],
+                &[ThreeOutcome::C],
+                &[],
+                5,
+            )
+            .expect("could not buy combo");
+
+        let combo_fee = with_fee.fees_collected() - fees_before_combo;
+        assert!(combo_fee > 0.0);
+        assert!((combo_fee - 0.05 * without_fee_trade.net_cost).abs() < 1e-9);
+        assert!(with_fee_trade.net_cost > without_fee_trade.net_cost);
+    }
 }
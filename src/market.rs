@@ -16,6 +16,43 @@ pub trait Market {
     fn payout_per_share(&self, outcome: Self::Outcome) -> Result<f64, Self::Error>;
 }
 
+/// Result of a combinatorial trade: the currency paid (positive) or received
+/// (negative), and the combined price of the set that was bought, i.e. the
+/// sum of its members' individual prices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComboTrade {
+    pub net_cost: f64,
+    pub combined_price: f64,
+}
+
+/// A market whose outcomes can be traded as partitioned groups, letting a
+/// trader express positions like "either A or B happens" in a single atomic
+/// trade.
+pub trait CombinatorialMarket: Market {
+    /// Buys `amount` shares of every outcome in `buy_set` while selling
+    /// `amount` shares of every outcome in `sell_set`, leaving `keep_set`
+    /// untouched. `buy_set`, `sell_set` and `keep_set` must together
+    /// partition the full outcome set, and `buy_set`/`sell_set` must both be
+    /// non-empty.
+    fn buy_combo(
+        &mut self,
+        buy_set: &[Self::Outcome],
+        sell_set: &[Self::Outcome],
+        keep_set: &[Self::Outcome],
+        amount: u64,
+    ) -> Result<ComboTrade, Self::Error>;
+
+    /// The inverse of `buy_combo`: sells `amount` shares of every outcome in
+    /// `buy_set` while buying `amount` shares of every outcome in `sell_set`.
+    fn sell_combo(
+        &mut self,
+        buy_set: &[Self::Outcome],
+        sell_set: &[Self::Outcome],
+        keep_set: &[Self::Outcome],
+        amount: u64,
+    ) -> Result<ComboTrade, Self::Error>;
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumCount, EnumIter)]
 pub enum BinaryOutcome {
     Yes,
@@ -0,0 +1,118 @@
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::lmsr::LmsrMarket;
+
+/// Which side of the book a `LimitOrder` rests on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// A resting offer to trade `shares` of `outcome` at `limit_price` or
+/// better.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimitOrder<T> {
+    pub outcome: T,
+    pub side: Side,
+    pub limit_price: f64,
+    pub shares: u64,
+}
+
+/// Resting bids and asks per outcome. Bids are kept sorted from highest to
+/// lowest price and asks from lowest to highest, so the best-priced order
+/// for an outcome is always at the front of its `Vec`.
+pub struct OrderBook<T: EnumCount + IntoEnumIterator + Copy + Eq> {
+    bids: Vec<Vec<LimitOrder<T>>>,
+    asks: Vec<Vec<LimitOrder<T>>>,
+}
+
+impl<T> OrderBook<T>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            bids: vec![Vec::new(); T::COUNT],
+            asks: vec![Vec::new(); T::COUNT],
+        }
+    }
+
+    fn outcome_index(outcome: T) -> usize {
+        LmsrMarket::<T>::outcome_index(outcome)
+    }
+
+    /// Adds a resting limit order, inserting it in price order.
+    pub fn place(&mut self, order: LimitOrder<T>) {
+        let i = Self::outcome_index(order.outcome);
+        match order.side {
+            Side::Buy => {
+                let bids = &mut self.bids[i];
+                let pos = bids
+                    .iter()
+                    .position(|o| o.limit_price < order.limit_price)
+                    .unwrap_or(bids.len());
+                bids.insert(pos, order);
+            }
+            Side::Sell => {
+                let asks = &mut self.asks[i];
+                let pos = asks
+                    .iter()
+                    .position(|o| o.limit_price > order.limit_price)
+                    .unwrap_or(asks.len());
+                asks.insert(pos, order);
+            }
+        }
+    }
+
+    /// Fills up to `amount` shares from the single best resting ask for
+    /// `outcome`, provided its `limit_price` is strictly below `max_price`.
+    /// Returns the shares filled and the amount paid, or `None` if there is
+    /// no ask priced below `max_price`.
+    pub fn fill_best_ask(&mut self, outcome: T, max_price: f64, amount: u64) -> Option<(u64, f64)> {
+        let i = Self::outcome_index(outcome);
+        let best = self.asks[i].first_mut()?;
+        if best.limit_price >= max_price {
+            return None;
+        }
+
+        let filled = amount.min(best.shares);
+        let cost = filled as f64 * best.limit_price;
+        best.shares -= filled;
+        if best.shares == 0 {
+            self.asks[i].remove(0);
+        }
+
+        Some((filled, cost))
+    }
+
+    /// Fills up to `amount` shares against the single best resting bid for
+    /// `outcome`, provided its `limit_price` is strictly above `min_price`.
+    /// Returns the shares filled and the amount received, or `None` if there
+    /// is no bid priced above `min_price`.
+    pub fn fill_best_bid(&mut self, outcome: T, min_price: f64, amount: u64) -> Option<(u64, f64)> {
+        let i = Self::outcome_index(outcome);
+        let best = self.bids[i].first_mut()?;
+        if best.limit_price <= min_price {
+            return None;
+        }
+
+        let filled = amount.min(best.shares);
+        let revenue = filled as f64 * best.limit_price;
+        best.shares -= filled;
+        if best.shares == 0 {
+            self.bids[i].remove(0);
+        }
+
+        Some((filled, revenue))
+    }
+}
+
+impl<T> Default for OrderBook<T>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
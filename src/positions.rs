@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::lmsr::{LmsrError, LmsrMarket};
+
+/// One account's open position in a market: their share balance per
+/// outcome, cost basis (total spent minus total received), and whether
+/// they have already claimed their resolution payout.
+#[derive(Debug, Clone)]
+struct Position {
+    shares: Vec<u64>,
+    cost_basis: f64,
+    claimed: bool,
+}
+
+impl Position {
+    fn new(outcome_count: usize) -> Self {
+        Self {
+            shares: vec![0; outcome_count],
+            cost_basis: 0.0,
+            claimed: false,
+        }
+    }
+}
+
+/// Plain, serializable view of a `Position`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionDTO {
+    pub shares: Vec<u64>,
+    pub cost_basis: f64,
+    pub claimed: bool,
+}
+
+impl From<Position> for PositionDTO {
+    fn from(value: Position) -> Self {
+        Self {
+            shares: value.shares,
+            cost_basis: value.cost_basis,
+            claimed: value.claimed,
+        }
+    }
+}
+
+impl From<PositionDTO> for Position {
+    fn from(value: PositionDTO) -> Self {
+        Self {
+            shares: value.shares,
+            cost_basis: value.cost_basis,
+            claimed: value.claimed,
+        }
+    }
+}
+
+/// Tracks every account's share balance and cost basis in a market, so
+/// per-participant P&L and resolution payouts can be computed.
+pub struct Positions<Account: Eq + Hash + Clone, T: EnumCount + IntoEnumIterator + Copy + Eq> {
+    entries: HashMap<Account, Position>,
+    _outcome: PhantomData<T>,
+}
+
+impl<Account, T> Positions<Account, T>
+where
+    Account: Eq + Hash + Clone,
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            _outcome: PhantomData,
+        }
+    }
+
+    fn entry(&mut self, account: &Account) -> &mut Position {
+        self.entries
+            .entry(account.clone())
+            .or_insert_with(|| Position::new(T::COUNT))
+    }
+
+    /// Records a buy of `amount` shares of `outcome` for `cost` currency.
+    pub fn record_buy(&mut self, account: &Account, outcome: T, amount: u64, cost: f64) {
+        let i = LmsrMarket::<T>::outcome_index(outcome);
+        let position = self.entry(account);
+        position.shares[i] += amount;
+        position.cost_basis += cost;
+    }
+
+    /// Records a sell of `amount` shares of `outcome` for `revenue` currency.
+    /// Fails with `LmsrError::InsufficientShares` if `account` does not hold
+    /// at least `amount` shares of `outcome`.
+    pub fn record_sell(
+        &mut self,
+        account: &Account,
+        outcome: T,
+        amount: u64,
+        revenue: f64,
+    ) -> Result<(), LmsrError> {
+        let i = LmsrMarket::<T>::outcome_index(outcome);
+        let position = self.entry(account);
+        if amount > position.shares[i] {
+            return Err(LmsrError::InsufficientShares);
+        }
+
+        position.shares[i] -= amount;
+        position.cost_basis -= revenue;
+        Ok(())
+    }
+
+    /// Shares of `outcome` held by `account`.
+    pub fn shares_held(&self, account: &Account, outcome: T) -> u64 {
+        let i = LmsrMarket::<T>::outcome_index(outcome);
+        self.entries.get(account).map_or(0, |p| p.shares[i])
+    }
+
+    /// Realized P&L for a fully closed position: zero while `account` still
+    /// holds shares of any outcome, otherwise the net of everything spent
+    /// and received.
+    pub fn realized_pnl(&self, account: &Account) -> f64 {
+        match self.entries.get(account) {
+            Some(position) if position.shares.iter().all(|&s| s == 0) => -position.cost_basis,
+            _ => 0.0,
+        }
+    }
+
+    /// Unrealized P&L given the current price of every outcome: what the
+    /// held shares are worth right now, minus their cost basis.
+    pub fn unrealized_pnl(&self, account: &Account, prices: &[f64]) -> f64 {
+        let Some(position) = self.entries.get(account) else {
+            return 0.0;
+        };
+
+        let market_value: f64 = position
+            .shares
+            .iter()
+            .zip(prices)
+            .map(|(&shares, &price)| shares as f64 * price)
+            .sum();
+
+        market_value - position.cost_basis
+    }
+
+    pub fn is_claimed(&self, account: &Account) -> bool {
+        self.entries.get(account).is_some_and(|p| p.claimed)
+    }
+
+    pub fn mark_claimed(&mut self, account: &Account) {
+        self.entry(account).claimed = true;
+    }
+}
+
+impl<Account, T> Default for Positions<Account, T>
+where
+    Account: Eq + Hash + Clone,
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plain, serializable view of a `Positions` map.
+pub struct PositionsDTO<Account, T>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    pub entries: Vec<(Account, PositionDTO)>,
+    _outcome: PhantomData<T>,
+}
+
+impl<Account, T> From<Positions<Account, T>> for PositionsDTO<Account, T>
+where
+    Account: Eq + Hash + Clone,
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    fn from(value: Positions<Account, T>) -> Self {
+        Self {
+            entries: value
+                .entries
+                .into_iter()
+                .map(|(account, position)| (account, position.into()))
+                .collect(),
+            _outcome: PhantomData,
+        }
+    }
+}
+
+impl<Account, T> From<PositionsDTO<Account, T>> for Positions<Account, T>
+where
+    Account: Eq + Hash + Clone,
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    fn from(value: PositionsDTO<Account, T>) -> Self {
+        Self {
+            entries: value
+                .entries
+                .into_iter()
+                .map(|(account, position)| (account, position.into()))
+                .collect(),
+            _outcome: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::hash::Hash;
+
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::lmsr::{LmsrError, LmsrMarket, LmsrMarketDTO};
+use crate::market::Market;
+use crate::positions::{Positions, PositionsDTO};
+
+/// Plain, serializable view of a `SettledMarket`.
+pub struct SettledMarketDTO<T, Account>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+{
+    pub market: LmsrMarketDTO<T>,
+    pub positions: PositionsDTO<Account, T>,
+}
+
+impl<T, Account> From<SettledMarket<T, Account>> for SettledMarketDTO<T, Account>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+    Account: Eq + Hash + Clone,
+{
+    fn from(value: SettledMarket<T, Account>) -> Self {
+        Self {
+            market: value.market.serialize(),
+            positions: value.positions.into(),
+        }
+    }
+}
+
+impl<T, Account> From<SettledMarketDTO<T, Account>> for SettledMarket<T, Account>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+    Account: Eq + Hash + Clone,
+{
+    fn from(value: SettledMarketDTO<T, Account>) -> Self {
+        Self {
+            market: value.market.into(),
+            positions: value.positions.into(),
+        }
+    }
+}
+
+/// Wraps an `LmsrMarket` with per-account position tracking, so trades can
+/// be attributed to a trader and resolution payouts settled individually
+/// instead of only tracking aggregate shares.
+pub struct SettledMarket<T: EnumCount + IntoEnumIterator + Copy + Eq, Account: Eq + Hash + Clone> {
+    market: LmsrMarket<T>,
+    positions: Positions<Account, T>,
+}
+
+impl<T, Account> SettledMarket<T, Account>
+where
+    T: EnumCount + IntoEnumIterator + Copy + Eq,
+    Account: Eq + Hash + Clone,
+{
+    pub fn new(liquidity: f64) -> Self {
+        Self {
+            market: LmsrMarket::new(liquidity),
+            positions: Positions::new(),
+        }
+    }
+
+    pub fn buy(&mut self, account: &Account, outcome: T, amount: u64) -> Result<f64, LmsrError> {
+        let cost = self.market.buy(outcome, amount)?;
+        self.positions.record_buy(account, outcome, amount, cost);
+        Ok(cost)
+    }
+
+    pub fn sell(&mut self, account: &Account, outcome: T, amount: u64) -> Result<f64, LmsrError> {
+        if amount > self.positions.shares_held(account, outcome) {
+            return Err(LmsrError::InsufficientShares);
+        }
+
+        let revenue = self.market.sell(outcome, amount)?;
+        self.positions.record_sell(account, outcome, amount, revenue)?;
+        Ok(revenue)
+    }
+
+    pub fn resolve(&mut self, winning_outcome: T) -> Result<(), LmsrError> {
+        self.market.resolve(winning_outcome)
+    }
+
+    /// Realized P&L for `account`'s fully closed position.
+    pub fn realized_pnl(&self, account: &Account) -> f64 {
+        self.positions.realized_pnl(account)
+    }
+
+    /// Unrealized P&L for `account` at current market prices.
+    pub fn unrealized_pnl(&self, account: &Account) -> Result<f64, LmsrError> {
+        let prices = T::iter()
+            .map(|outcome| self.market.price(outcome))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self.positions.unrealized_pnl(account, &prices))
+    }
+
+    /// Pays `account` their share of the resolved market's payout
+    /// (`held_shares_of_winning_outcome * payout_per_share`) and marks their
+    /// position as claimed so it cannot be claimed a second time.
+    pub fn claim(&mut self, account: &Account) -> Result<f64, LmsrError> {
+        let winning_outcome = self.market.resolved().ok_or(LmsrError::Unresolved)?;
+
+        if self.positions.is_claimed(account) {
+            return Err(LmsrError::AlreadyClaimed);
+        }
+
+        let held = self.positions.shares_held(account, winning_outcome);
+        let payout = if held == 0 {
+            0.0
+        } else {
+            held as f64 * self.market.payout_per_share(winning_outcome)?
+        };
+
+        self.positions.mark_claimed(account);
+        Ok(payout)
+    }
+
+    pub fn serialize(self) -> SettledMarketDTO<T, Account> {
+        self.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::market::BinaryOutcome;
+
+    use super::*;
+
+    #[test]
+    fn test_claim_pays_winning_shares() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        let cost = market.buy(&1, BinaryOutcome::Yes, 4).expect("could not buy");
+        market
+            .resolve(BinaryOutcome::Yes)
+            .expect("could not resolve market");
+
+        let payout = market.claim(&1).expect("could not claim");
+
+        assert_eq!(cost, payout);
+    }
+
+    #[test]
+    fn test_claim_twice_fails() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        market.buy(&1, BinaryOutcome::Yes, 4).expect("could not buy");
+        market
+            .resolve(BinaryOutcome::Yes)
+            .expect("could not resolve market");
+
+        market.claim(&1).expect("could not claim");
+        let err = market.claim(&1).expect_err("second claim should fail");
+
+        assert_eq!(err, LmsrError::AlreadyClaimed);
+    }
+
+    #[test]
+    fn test_claim_before_resolution_fails() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        market.buy(&1, BinaryOutcome::Yes, 4).expect("could not buy");
+        let err = market.claim(&1).expect_err("unresolved claim should fail");
+
+        assert_eq!(err, LmsrError::Unresolved);
+    }
+
+    #[test]
+    fn test_losing_position_claims_nothing() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        market.buy(&1, BinaryOutcome::No, 4).expect("could not buy");
+        market
+            .resolve(BinaryOutcome::Yes)
+            .expect("could not resolve market");
+
+        let payout = market.claim(&1).expect("could not claim");
+
+        assert_eq!(payout, 0.0);
+    }
+
+    #[test]
+    fn test_realized_pnl_zero_while_position_open() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        market.buy(&1, BinaryOutcome::Yes, 4).expect("could not buy");
+
+        assert_eq!(market.realized_pnl(&1), 0.0);
+    }
+
+    #[test]
+    fn test_realized_pnl_after_closing_position() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        let cost = market.buy(&1, BinaryOutcome::Yes, 4).expect("could not buy");
+        let revenue = market.sell(&1, BinaryOutcome::Yes, 4).expect("could not sell");
+
+        assert_eq!(market.realized_pnl(&1), revenue - cost);
+    }
+
+    #[test]
+    fn test_sell_rejects_account_without_a_position() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        // Account 2 holds shares, but account 1 has never bought any.
+        market.buy(&2, BinaryOutcome::Yes, 4).expect("could not buy");
+
+        let err = market
+            .sell(&1, BinaryOutcome::Yes, 1)
+            .expect_err("selling shares you don't hold should fail");
+
+        assert_eq!(err, LmsrError::InsufficientShares);
+    }
+
+    #[test]
+    fn test_sell_rejects_more_than_account_holds() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        market.buy(&1, BinaryOutcome::Yes, 2).expect("could not buy");
+        market.buy(&2, BinaryOutcome::Yes, 4).expect("could not buy");
+
+        let err = market
+            .sell(&1, BinaryOutcome::Yes, 3)
+            .expect_err("selling more than your own position should fail");
+
+        assert_eq!(err, LmsrError::InsufficientShares);
+    }
+
+    #[test]
+    fn test_unrealized_pnl_matches_price_move() {
+        let mut market = SettledMarket::<BinaryOutcome, u32>::new(10.0);
+
+        let cost = market.buy(&1, BinaryOutcome::Yes, 4).expect("could not buy");
+        // A second trader pushes the price up further.
+        market.buy(&2, BinaryOutcome::Yes, 4).expect("could not buy");
+
+        let current_price = Market::price(&market.market, BinaryOutcome::Yes)
+            .expect("could not determine price");
+        let unrealized = market
+            .unrealized_pnl(&1)
+            .expect("could not determine unrealized pnl");
+
+        assert_eq!(unrealized, 4.0 * current_price - cost);
+    }
+}